@@ -0,0 +1,111 @@
+//! Block layout: stacks children along a single axis (vertically by
+//! default), resolving relative dimensions against the parent content
+//! box. Runs parallel to `flex.rs`.
+
+use super::{
+    text, LayoutChild, LayoutTreeNode, LogicalPoint, LogicalSideOffsets, LogicalSize, RenderData,
+};
+use crate::dom::element::DynamicNode;
+use crate::dom::node::AnyNode;
+use crate::style::{BlockValues, ComputedValues, Direction};
+use crate::util::equal_rc::EqualRc;
+
+/// Clamp `value` to the optional min/max bounds.
+fn clamp(value: f32, min: Option<f32>, max: Option<f32>) -> f32 {
+    let mut value = value;
+    if let Some(max) = max {
+        value = value.min(max);
+    }
+    if let Some(min) = min {
+        value = value.max(min);
+    }
+    value
+}
+
+pub fn layout_block(
+    node: AnyNode,
+    values: &ComputedValues,
+    block: &BlockValues,
+    size: LogicalSize,
+) -> EqualRc<LayoutTreeNode> {
+    let horizontal = block.direction == Direction::Horizontal;
+    let padding = block.padding;
+
+    // Resolve the explicit dimensions against the incoming size, with
+    // `em` taken from the element's resolved text size. Percentages
+    // resolve against the parent content box; `Auto` defers to intrinsic
+    // sizing below. Resolution happens here, where the parent size is
+    // known, rather than at style time.
+    let em = values.text_size.get();
+    let explicit_width = block.width.resolve(size.width, em).map(|v| v.get());
+    let explicit_height = block.height.resolve(size.height, em).map(|v| v.get());
+
+    // The content box children lay out within.
+    let avail = LogicalSize::new(
+        explicit_width.unwrap_or(size.width) - padding.horizontal(),
+        explicit_height.unwrap_or(size.height) - padding.vertical(),
+    );
+
+    // Lay children out end-to-end along the main axis, tracking the used
+    // main extent.
+    let mut children = Vec::new();
+    let mut main_used = 0.0;
+    for (index, child) in node.children().enumerate() {
+        // Each child lays out in its own topo slot so per-node memos for
+        // siblings stay independent.
+        let layout = topo::call!(
+            {
+                match child {
+                    DynamicNode::Node(child) => super::layout_node(child.into(), avail),
+                    DynamicNode::Text(contents) => {
+                        text::layout_text_node(node, values, contents, avail)
+                    }
+                }
+            },
+            slot = index
+        );
+        let position = if horizontal {
+            LogicalPoint::new(padding.left + main_used, padding.top)
+        } else {
+            LogicalPoint::new(padding.left, padding.top + main_used)
+        };
+        if horizontal {
+            main_used += layout.size.width;
+        } else {
+            main_used += layout.size.height;
+        }
+        children.push(LayoutChild { position, layout });
+    }
+
+    // Intrinsic sizes when a dimension is `Auto`: the main axis hugs its
+    // content, while the cross axis fills the parent, matching how block
+    // boxes size in the other engines.
+    let auto_width = if horizontal {
+        main_used + padding.horizontal()
+    } else {
+        size.width
+    };
+    let auto_height = if horizontal {
+        size.height
+    } else {
+        main_used + padding.vertical()
+    };
+
+    let width = clamp(
+        explicit_width.unwrap_or(auto_width),
+        block.min_width.resolve(size.width, em).map(|v| v.get()),
+        block.max_width.resolve(size.width, em).map(|v| v.get()),
+    );
+    let height = clamp(
+        explicit_height.unwrap_or(auto_height),
+        block.min_height.resolve(size.height, em).map(|v| v.get()),
+        block.max_height.resolve(size.height, em).map(|v| v.get()),
+    );
+
+    EqualRc::new(LayoutTreeNode {
+        size: LogicalSize::new(width, height),
+        margin: block.margin,
+        render: RenderData::Node(node),
+        children,
+    })
+}
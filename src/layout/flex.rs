@@ -0,0 +1,390 @@
+//! Flexbox layout, implementing the subset of the CSS flexible box
+//! algorithm needed to grow and shrink children along a main axis and
+//! align them along the cross axis. Runs parallel to `block.rs`.
+
+use super::{text, LayoutChild, LayoutTreeNode, LogicalPoint, LogicalSize, RenderData};
+use crate::dom::element::DynamicNode;
+use crate::dom::node::AnyNode;
+use crate::style::{
+    AlignItems, ComputedValues, Direction, DisplayType, FlexValues, JustifyContent,
+};
+use crate::util::equal_rc::EqualRc;
+
+/// A flex item's DOM contents: either an element subtree or a run of
+/// anonymous text inheriting the container's computed values.
+enum FlexChild {
+    Node(AnyNode),
+    Text(String),
+}
+
+/// Lay out a flex child against its resolved size. Element children go
+/// through the per-node memo; text children are shaped against the
+/// container's computed values and published as a text render node,
+/// exactly as the block path does for its own text.
+fn layout_flex_child(
+    child: &FlexChild,
+    parent: AnyNode,
+    parent_values: &ComputedValues,
+    size: LogicalSize,
+) -> EqualRc<LayoutTreeNode> {
+    match child {
+        FlexChild::Node(node) => super::layout_node(*node, size),
+        FlexChild::Text(contents) => text::layout_text_node(parent, parent_values, contents, size),
+    }
+}
+
+/// The resolved min/max bounds along the main axis for a child, or
+/// `(None, None)` for display types that carry no explicit sizing.
+fn main_bounds(
+    values: &ComputedValues,
+    horizontal: bool,
+    avail: LogicalSize,
+) -> (Option<f32>, Option<f32>) {
+    let em = values.text_size.get();
+    let (min, max, parent) = match values.display {
+        DisplayType::Block(ref block) => {
+            if horizontal {
+                (block.min_width, block.max_width, avail.width)
+            } else {
+                (block.min_height, block.max_height, avail.height)
+            }
+        }
+        DisplayType::Flex(ref flex) => {
+            if horizontal {
+                (flex.min_width, flex.max_width, avail.width)
+            } else {
+                (flex.min_height, flex.max_height, avail.height)
+            }
+        }
+        DisplayType::Inline(_) => return (None, None),
+    };
+    (
+        min.resolve(parent, em).map(|v| v.get()),
+        max.resolve(parent, em).map(|v| v.get()),
+    )
+}
+
+/// Clamp `value` to the optional min/max bounds, matching the behavior
+/// of the block layout path.
+fn clamp(value: f32, min: Option<f32>, max: Option<f32>) -> f32 {
+    let mut value = value;
+    if let Some(max) = max {
+        value = value.min(max);
+    }
+    if let Some(min) = min {
+        value = value.max(min);
+    }
+    value
+}
+
+/// The inputs the main-axis distribution needs for one flex item,
+/// independent of the DOM so the algorithm can be tested in isolation.
+#[derive(Clone, Copy)]
+struct MainInput {
+    base: f32,
+    grow: f32,
+    shrink: f32,
+    min: Option<f32>,
+    max: Option<f32>,
+}
+
+/// Resolve the final main-axis size of each item on a single flex line:
+/// grow when there is positive free space (in proportion to `grow`) or
+/// shrink when negative (weighted by `shrink * base`), then clamp each
+/// result to a non-negative value and to the item's own min/max bounds.
+fn resolve_main_sizes(items: &[MainInput], container_main: f32) -> Vec<f32> {
+    let total_base: f32 = items.iter().map(|item| item.base).sum();
+    let free = container_main - total_base;
+
+    let total_grow: f32 = items.iter().map(|item| item.grow).sum();
+    let total_weight: f32 = items.iter().map(|item| item.shrink * item.base).sum();
+
+    items
+        .iter()
+        .map(|item| {
+            let main = if free > 0.0 && total_grow > 0.0 {
+                item.base + free * (item.grow / total_grow)
+            } else if free < 0.0 && total_weight > 0.0 {
+                item.base + free * (item.shrink * item.base / total_weight)
+            } else {
+                item.base
+            };
+            clamp(main.max(0.0), item.min, item.max)
+        })
+        .collect()
+}
+
+/// State tracked per child while resolving the flex line.
+struct Item {
+    /// The child's index among the container's children, used as the
+    /// topo slot so each child memoizes independently.
+    index: usize,
+    child: FlexChild,
+    grow: f32,
+    shrink: f32,
+    base: f32,
+    main: f32,
+    cross: f32,
+    /// Resolved min/max bounds on the main size of this child.
+    min: Option<f32>,
+    max: Option<f32>,
+}
+
+pub fn layout_flex(
+    node: AnyNode,
+    values: &ComputedValues,
+    flex: &FlexValues,
+    size: LogicalSize,
+) -> EqualRc<LayoutTreeNode> {
+    let horizontal = flex.direction == Direction::Horizontal;
+
+    // The main/cross dimensions of the container, reduced by padding.
+    let padding = flex.padding;
+    let avail = LogicalSize::new(
+        size.width - padding.horizontal(),
+        size.height - padding.vertical(),
+    );
+    let container_main = if horizontal { avail.width } else { avail.height };
+    let container_cross = if horizontal { avail.height } else { avail.width };
+
+    // (1) Compute each child's base size along the main axis, either
+    // from `flex_basis` or its intrinsic size under the container. Both
+    // element and text children participate, matching the block path.
+    let mut items = Vec::new();
+    for (index, child) in node.children().enumerate() {
+        let (flex_child, child_values, bounds) = match child {
+            DynamicNode::Node(child) => {
+                let child_values = child.computed_values().get().unwrap();
+                let bounds = main_bounds(&child_values, horizontal, avail);
+                (FlexChild::Node(child.into()), child_values, bounds)
+            }
+            DynamicNode::Text(contents) => {
+                // Anonymous text inherits the container's values and
+                // carries the default (non-growing) flex attributes.
+                (FlexChild::Text(contents.to_owned()), values.clone(), (None, None))
+            }
+        };
+        let intrinsic = topo::call!(
+            { layout_flex_child(&flex_child, node, values, avail) },
+            slot = index
+        );
+        let base = match child_values.flex_basis {
+            Some(basis) => basis.get(),
+            None => {
+                if horizontal {
+                    intrinsic.size.width
+                } else {
+                    intrinsic.size.height
+                }
+            }
+        };
+        items.push(Item {
+            index,
+            child: flex_child,
+            grow: child_values.flex_grow,
+            shrink: child_values.flex_shrink,
+            base,
+            main: base,
+            cross: if horizontal {
+                intrinsic.size.height
+            } else {
+                intrinsic.size.width
+            },
+            min: bounds.0,
+            max: bounds.1,
+        });
+    }
+
+    // Partition items into flex lines. With `wrap` disabled everything
+    // lands on a single line; otherwise a new line starts whenever the
+    // next item's base would overflow the container's main size.
+    let mut lines: Vec<Vec<Item>> = Vec::new();
+    if flex.wrap {
+        let mut current: Vec<Item> = Vec::new();
+        let mut used = 0.0;
+        for item in items {
+            if !current.is_empty() && used + item.base > container_main {
+                lines.push(std::mem::take(&mut current));
+                used = 0.0;
+            }
+            used += item.base;
+            current.push(item);
+        }
+        if !current.is_empty() {
+            lines.push(current);
+        }
+    } else {
+        lines.push(items);
+    }
+
+    let mut children = Vec::new();
+    let mut cross_cursor = 0.0;
+    for mut line in lines {
+        // (2, 3) Distribute the line's free space by growing or shrinking
+        // each item, then clamp the result to `>= 0` and the child's own
+        // min/max bounds.
+        let inputs: Vec<MainInput> = line
+            .iter()
+            .map(|item| MainInput {
+                base: item.base,
+                grow: item.grow,
+                shrink: item.shrink,
+                min: item.min,
+                max: item.max,
+            })
+            .collect();
+        for (item, main) in line.iter_mut().zip(resolve_main_sizes(&inputs, container_main)) {
+            item.main = main;
+        }
+
+        // The cross extent of a line: the whole container for a single
+        // unwrapped line (so `Stretch` fills it as before), otherwise
+        // the tallest item on the line.
+        let line_cross = if flex.wrap {
+            line.iter().map(|item| item.cross).fold(0.0, f32::max)
+        } else {
+            container_cross
+        };
+
+        // (4) Resolve the cross-axis size of each item per `align_items`.
+        for item in &mut line {
+            if flex.align_items == AlignItems::Stretch {
+                item.cross = line_cross;
+            }
+        }
+
+        // (5) Position items end-to-end, inserting gaps per
+        // `justify_content`.
+        let used_main: f32 = line.iter().map(|item| item.main).sum();
+        let count = line.len() as f32;
+        let slack = (container_main - used_main).max(0.0);
+        let (mut cursor, gap) = match flex.justify_content {
+            JustifyContent::FlexStart => (0.0, 0.0),
+            JustifyContent::Center => (slack / 2.0, 0.0),
+            JustifyContent::FlexEnd => (slack, 0.0),
+            JustifyContent::SpaceBetween => {
+                if count > 1.0 {
+                    (0.0, slack / (count - 1.0))
+                } else {
+                    (0.0, 0.0)
+                }
+            }
+            JustifyContent::SpaceAround => {
+                if count > 0.0 {
+                    let gap = slack / count;
+                    (gap / 2.0, gap)
+                } else {
+                    (0.0, 0.0)
+                }
+            }
+        };
+
+        for item in line {
+            let child_size = if horizontal {
+                LogicalSize::new(item.main, item.cross)
+            } else {
+                LogicalSize::new(item.cross, item.main)
+            };
+            let layout = topo::call!(
+                { layout_flex_child(&item.child, node, values, child_size) },
+                slot = item.index
+            );
+
+            let cross_offset = cross_cursor
+                + match flex.align_items {
+                    AlignItems::Stretch | AlignItems::Start => 0.0,
+                    AlignItems::Center => (line_cross - item.cross) / 2.0,
+                    AlignItems::End => line_cross - item.cross,
+                };
+
+            let position = if horizontal {
+                LogicalPoint::new(padding.left + cursor, padding.top + cross_offset)
+            } else {
+                LogicalPoint::new(padding.left + cross_offset, padding.top + cursor)
+            };
+
+            children.push(LayoutChild { position, layout });
+            cursor += item.main + gap;
+        }
+
+        cross_cursor += line_cross;
+    }
+
+    // Resolve the container's own size, honoring the explicit sizes and
+    // the min/max clamps. Percentages resolve against the incoming size
+    // and `em` against the element's resolved text size.
+    let em = values.text_size.get();
+    let width = clamp(
+        flex.width.resolve(size.width, em).map(|w| w.get()).unwrap_or(size.width),
+        flex.min_width.resolve(size.width, em).map(|v| v.get()),
+        flex.max_width.resolve(size.width, em).map(|v| v.get()),
+    );
+    let height = clamp(
+        flex.height.resolve(size.height, em).map(|h| h.get()).unwrap_or(size.height),
+        flex.min_height.resolve(size.height, em).map(|v| v.get()),
+        flex.max_height.resolve(size.height, em).map(|v| v.get()),
+    );
+
+    EqualRc::new(LayoutTreeNode {
+        size: LogicalSize::new(width, height),
+        margin: flex.margin,
+        render: RenderData::Node(node),
+        children,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_main_sizes, MainInput};
+
+    fn item(base: f32, grow: f32, shrink: f32) -> MainInput {
+        MainInput {
+            base,
+            grow,
+            shrink,
+            min: None,
+            max: None,
+        }
+    }
+
+    #[test]
+    fn positive_free_space_is_shared_by_grow() {
+        // 40px free shared 1:3 on top of equal 50px bases.
+        let items = [item(50.0, 1.0, 1.0), item(50.0, 3.0, 1.0)];
+        assert_eq!(resolve_main_sizes(&items, 140.0), vec![60.0, 80.0]);
+    }
+
+    #[test]
+    fn no_grow_leaves_free_space_unused() {
+        let items = [item(50.0, 0.0, 1.0), item(50.0, 0.0, 1.0)];
+        assert_eq!(resolve_main_sizes(&items, 200.0), vec![50.0, 50.0]);
+    }
+
+    #[test]
+    fn negative_free_space_shrinks_weighted_by_base() {
+        // 60px overflow removed, weighted by shrink * base (equal here).
+        let items = [item(100.0, 0.0, 1.0), item(100.0, 0.0, 1.0)];
+        assert_eq!(resolve_main_sizes(&items, 140.0), vec![70.0, 70.0]);
+    }
+
+    #[test]
+    fn shrink_is_clamped_to_non_negative() {
+        // A single highly-shrinkable item would go negative; it clamps
+        // to zero instead.
+        let items = [item(50.0, 0.0, 1.0)];
+        assert_eq!(resolve_main_sizes(&items, -100.0), vec![0.0]);
+    }
+
+    #[test]
+    fn resolved_size_respects_min_and_max() {
+        let items = [MainInput {
+            base: 50.0,
+            grow: 1.0,
+            shrink: 1.0,
+            min: None,
+            max: Some(70.0),
+        }];
+        // Would grow to 200 but is capped at its max.
+        assert_eq!(resolve_main_sizes(&items, 200.0), vec![70.0]);
+    }
+}
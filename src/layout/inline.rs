@@ -0,0 +1,48 @@
+//! Inline layout: flows children left-to-right and sizes the box to its
+//! content. Inline boxes carry no explicit dimensions of their own, so
+//! any relative sizing on their children resolves against the incoming
+//! size when those children lay themselves out.
+
+use super::{
+    text, LayoutChild, LayoutTreeNode, LogicalPoint, LogicalSideOffsets, LogicalSize, RenderData,
+};
+use crate::dom::element::DynamicNode;
+use crate::dom::node::AnyNode;
+use crate::style::ComputedValues;
+use crate::util::equal_rc::EqualRc;
+
+pub fn layout_inline(
+    node: AnyNode,
+    values: &ComputedValues,
+    size: LogicalSize,
+) -> EqualRc<LayoutTreeNode> {
+    let mut children = Vec::new();
+    let mut width = 0.0;
+    let mut height = 0.0f32;
+    for (index, child) in node.children().enumerate() {
+        // Each child lays out in its own topo slot so per-node memos for
+        // siblings stay independent.
+        let layout = topo::call!(
+            {
+                match child {
+                    DynamicNode::Node(child) => super::layout_node(child.into(), size),
+                    DynamicNode::Text(contents) => {
+                        text::layout_text_node(node, values, contents, size)
+                    }
+                }
+            },
+            slot = index
+        );
+        let position = LogicalPoint::new(width, 0.0);
+        width += layout.size.width;
+        height = height.max(layout.size.height);
+        children.push(LayoutChild { position, layout });
+    }
+
+    EqualRc::new(LayoutTreeNode {
+        size: LogicalSize::new(width, height),
+        margin: LogicalSideOffsets::new_all_same(0.0),
+        render: RenderData::Node(node),
+        children,
+    })
+}
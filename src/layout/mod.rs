@@ -13,9 +13,11 @@ use font_kit::sources::mem::MemSource;
 use moxie::embed::Runtime;
 use moxie::*;
 use skribo::{FontCollection, FontFamily, FontRef};
+use std::cell::RefCell;
 use std::sync::Arc;
 
 mod block;
+mod flex;
 mod inline;
 mod text;
 
@@ -45,13 +47,50 @@ pub struct TextFragment {
     pub glyphs: Vec<Glyph>,
 }
 
+/// A single wrapped line of text. Glyph offsets inside each fragment are
+/// relative to the start of the line.
+pub struct LayoutLine {
+    pub fragments: Vec<TextFragment>,
+    /// The vertical offset of this line's baseline from the top of the
+    /// text node.
+    pub baseline: f32,
+    /// The advance width occupied by the glyphs on this line.
+    pub width: f32,
+}
+
 /// Information passed to the renderer for rendering text.
 pub struct LayoutText {
-    pub fragments: Vec<TextFragment>,
+    pub lines: Vec<LayoutLine>,
     /// The text size of the text.
     pub size: f32,
 }
 
+impl LayoutText {
+    /// A flat view of every glyph as fragments, with each line's
+    /// baseline folded into the glyph offsets. Renderers that predate
+    /// the line-based layout can iterate this instead of walking
+    /// [`LayoutText::lines`] directly.
+    pub fn fragments(&self) -> Vec<TextFragment> {
+        let mut fragments = Vec::new();
+        for line in &self.lines {
+            for fragment in &line.fragments {
+                fragments.push(TextFragment {
+                    font: fragment.font.clone(),
+                    glyphs: fragment
+                        .glyphs
+                        .iter()
+                        .map(|glyph| Glyph {
+                            index: glyph.index,
+                            offset: LogicalPoint::new(glyph.offset.x, glyph.offset.y + line.baseline),
+                        })
+                        .collect(),
+                });
+            }
+        }
+        fragments
+    }
+}
+
 pub enum RenderData {
     Text { text: LayoutText, parent: AnyNode },
     Node(AnyNode),
@@ -66,46 +105,152 @@ pub struct LayoutTreeNode {
     pub children: Vec<LayoutChild>,
 }
 
+/// A registry of font faces used to build shaping collections. Holds
+/// the embedded faces and, behind the `system-fonts` feature, the
+/// platform's system fonts, and caches constructed `FontCollection`s so
+/// repeated layouts don't reload faces.
+///
+/// Applications may register additional faces at startup via
+/// [`FontSource::register`].
+pub struct FontSource {
+    faces: RefCell<Vec<Handle>>,
+    mem: RefCell<MemSource>,
+    cache: RefCell<Vec<((FamilyName, Properties), EqualRc<FontCollection>)>>,
+}
+
+impl FontSource {
+    /// Build a source seeded with the embedded Comic Neue face.
+    pub fn new() -> FontSource {
+        let regular = Handle::from_memory(Arc::new(REGULAR_FONT.to_vec()), 0);
+        let faces = vec![regular];
+        let mem = MemSource::from_fonts(faces.clone().into_iter()).unwrap();
+        FontSource {
+            faces: RefCell::new(faces),
+            mem: RefCell::new(mem),
+            cache: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Register an additional embedded face, invalidating the cache.
+    pub fn register(&self, handle: Handle) {
+        let mut faces = self.faces.borrow_mut();
+        faces.push(handle);
+        *self.mem.borrow_mut() = MemSource::from_fonts(faces.clone().into_iter()).unwrap();
+        self.cache.borrow_mut().clear();
+    }
+
+    /// The families appended after the requested family so skribo can
+    /// fall back per cluster when the primary face lacks a glyph.
+    fn fallback_chain() -> [FamilyName; 3] {
+        [
+            FamilyName::SansSerif,
+            FamilyName::Serif,
+            FamilyName::Monospace,
+        ]
+    }
+
+    /// Select a face for a single family, consulting the embedded faces
+    /// first and, behind the feature flag, the system fonts.
+    fn select(&self, family: &FamilyName, properties: &Properties) -> Option<Handle> {
+        if let Ok(handle) = self
+            .mem
+            .borrow()
+            .select_best_match(std::slice::from_ref(family), properties)
+        {
+            return Some(handle);
+        }
+        #[cfg(feature = "system-fonts")]
+        {
+            font_kit::source::SystemSource::new()
+                .select_best_match(std::slice::from_ref(family), properties)
+                .ok()
+        }
+        #[cfg(not(feature = "system-fonts"))]
+        None
+    }
+
+    /// Return a collection with `family` first and the fallback chain
+    /// appended, reusing a cached collection when one exists for the
+    /// same (family, properties, fallback-set) key.
+    pub fn collection_for(
+        &self,
+        family: &FamilyName,
+        properties: &Properties,
+    ) -> EqualRc<FontCollection> {
+        let key = (family.clone(), *properties);
+        if let Some((_, collection)) = self.cache.borrow().iter().find(|(k, _)| *k == key) {
+            return collection.clone();
+        }
+
+        let mut collection = FontCollection::new();
+        for name in std::iter::once(family.clone()).chain(Self::fallback_chain().iter().cloned()) {
+            if let Some(handle) = self.select(&name, properties) {
+                if let Ok(font) = handle.load() {
+                    collection.add_family(FontFamily::new_from_font(font));
+                }
+            }
+        }
+
+        let collection = EqualRc::new(collection);
+        self.cache.borrow_mut().push((key, collection.clone()));
+        collection
+    }
+}
+
+/// Lay out a single node, memoizing the produced subtree under the
+/// node's identity together with its own computed values and the
+/// incoming constraint. A node is only re-laid-out when one of those
+/// inputs changes, so a steady-state frame costs roughly the number of
+/// nodes whose inputs actually changed.
+///
+/// Children are laid out only from inside this memoized closure, so a
+/// cache hit returns the stored subtree without visiting them; a change
+/// below this node is picked up only when it also changes this node's
+/// identity, computed values or incoming size. Each child is laid out in
+/// its own topo slot (see the layout modules' child loops) so sibling
+/// caches stay independent.
+#[illicit::from_env(fonts: &EqualRc<FontSource>)]
+pub(crate) fn layout_node(node: AnyNode, size: LogicalSize) -> EqualRc<LayoutTreeNode> {
+    let values = node.computed_values().get().unwrap();
+    let collection = fonts.collection_for(&values.font_family, &values.font_properties);
+    memo!((node, values.clone(), size), |_| {
+        illicit::child_env!(EqualRc<FontCollection> => collection.clone()).enter(|| {
+            match values.display {
+                DisplayType::Block(ref block) => block::layout_block(node, &values, block, size),
+                DisplayType::Flex(ref flex) => flex::layout_flex(node, &values, flex, size),
+                DisplayType::Inline(_) => inline::layout_inline(node, &values, size),
+            }
+        })
+    })
+}
+
 /// Used to build the layout tree, with internal caching for
 /// performance.
 pub struct LayoutEngine {
     runtime: Runtime<fn() -> EqualRc<LayoutTreeNode>>,
+    fonts: EqualRc<FontSource>,
 }
 
 impl LayoutEngine {
     pub fn new() -> LayoutEngine {
         LayoutEngine {
             runtime: Runtime::new(LayoutEngine::run_layout),
+            fonts: EqualRc::new(FontSource::new()),
         }
     }
 
+    /// The font registry, so applications can register custom faces at
+    /// startup before the first layout pass.
+    pub fn fonts(&self) -> &EqualRc<FontSource> {
+        &self.fonts
+    }
+
     #[illicit::from_env(node: &Node<Window>, size: &LogicalSize)]
     fn run_layout() -> EqualRc<LayoutTreeNode> {
-        let collection = once!(|| {
-            let mut collection = FontCollection::new();
-            let regular_handle = Handle::from_memory(Arc::new(REGULAR_FONT.to_vec()), 0);
-            let source = MemSource::from_fonts(vec![regular_handle].into_iter()).unwrap();
-            let font = source
-                .select_best_match(&[FamilyName::SansSerif], &Properties::new())
-                .unwrap()
-                .load()
-                .unwrap();
-            collection.add_family(FontFamily::new_from_font(font));
-
-            EqualRc::new(collection)
-        });
-
-        illicit::child_env!(EqualRc<FontCollection> => collection).enter(|| {
-            topo::call!({
-                let values = node.computed_values().get().unwrap();
-                match values.display {
-                    DisplayType::Block(ref block) => {
-                        block::layout_block(node.into(), &values, block, *size)
-                    }
-                    DisplayType::Inline(_) => inline::layout_inline(node.into(), &values, *size),
-                }
-            },)
-        })
+        // Lay the root out through the per-node memo; every descendant is
+        // reached recursively through `layout_node` so each caches under
+        // its own inputs.
+        layout_node(node.into(), *size)
     }
 
     /// Perform a layout step based on the new DOM and content size, and
@@ -113,7 +258,8 @@ impl LayoutEngine {
     pub fn layout(&mut self, node: Node<Window>, size: LogicalSize) -> EqualRc<LayoutTreeNode> {
         illicit::child_env! (
             Node<Window> => node,
-            LogicalSize => size
+            LogicalSize => size,
+            EqualRc<FontSource> => self.fonts.clone()
         )
         .enter(|| topo::call!({ self.runtime.run_once() },))
     }
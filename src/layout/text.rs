@@ -0,0 +1,375 @@
+//! Text layout: shaping a string into glyphs and breaking it into lines
+//! that fit the available width, then aligning each line.
+
+use super::{
+    Glyph, LayoutLine, LayoutText, LayoutTreeNode, LogicalPoint, LogicalSideOffsets, LogicalSize,
+    RenderData, TextFragment,
+};
+use crate::dom::node::AnyNode;
+use crate::style::{ComputedValues, TextAlign};
+use crate::util::equal_rc::EqualRc;
+use moxie::*;
+use skribo::{FontCollection, FontRef, LayoutSession, TextStyle};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A single shaped glyph together with the information needed to break
+/// and align lines.
+struct Shaped {
+    font: FontRef,
+    index: u32,
+    /// Horizontal advance of this glyph.
+    advance: f32,
+    /// Whether a line may break *before* this glyph (i.e. it follows
+    /// whitespace).
+    break_before: bool,
+    /// Whether this glyph is itself whitespace, and therefore collapses
+    /// at the end of a wrapped line.
+    whitespace: bool,
+}
+
+/// Shape the whole string into a flat run of glyphs, recording break
+/// opportunities so the line breaker can operate on advances alone.
+fn shape(text: &str, size: f32, collection: &FontCollection) -> Vec<Shaped> {
+    let style = TextStyle { size };
+    let session = LayoutSession::create(text, &style, collection);
+
+    let mut shaped = Vec::new();
+    let mut prev_whitespace = false;
+    for run in session.iter_all() {
+        let font = run.font().clone();
+        let glyphs: Vec<_> = run.glyphs().collect();
+        for (i, glyph) in glyphs.iter().enumerate() {
+            let next = glyphs
+                .get(i + 1)
+                .map(|g| g.offset.x)
+                .unwrap_or_else(|| run.advance());
+            let advance = next - glyph.offset.x;
+            let whitespace = text[glyph.cluster as usize..]
+                .chars()
+                .next()
+                .map(char::is_whitespace)
+                .unwrap_or(false);
+            shaped.push(Shaped {
+                font: font.clone(),
+                index: glyph.glyph_id,
+                advance,
+                break_before: prev_whitespace,
+                whitespace,
+            });
+            prev_whitespace = whitespace;
+        }
+    }
+    shaped
+}
+
+/// The per-glyph measurements the line breaker needs, stripped of any
+/// dependence on the shaped font so the algorithm can be reasoned about
+/// — and tested — in isolation.
+#[derive(Clone, Copy)]
+struct Measure {
+    advance: f32,
+    break_before: bool,
+    whitespace: bool,
+}
+
+impl From<&Shaped> for Measure {
+    fn from(glyph: &Shaped) -> Measure {
+        Measure {
+            advance: glyph.advance,
+            break_before: glyph.break_before,
+            whitespace: glyph.whitespace,
+        }
+    }
+}
+
+/// Break `glyphs` greedily into lines no wider than `available`,
+/// returning for each line the half-open glyph range `start..end` and its width
+/// measured without trailing whitespace.
+fn break_lines(glyphs: &[Shaped], available: f32) -> Vec<(usize, usize, f32)> {
+    let measures: Vec<Measure> = glyphs.iter().map(Measure::from).collect();
+    break_measures(&measures, available)
+}
+
+/// The greedy line-breaking core, operating purely on glyph advances
+/// and break opportunities.
+fn break_measures(glyphs: &[Measure], available: f32) -> Vec<(usize, usize, f32)> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut width = 0.0;
+    let mut last_break = None;
+
+    let mut i = 0;
+    while i < glyphs.len() {
+        let glyph = &glyphs[i];
+        if glyph.break_before {
+            last_break = Some(i);
+        }
+        if width + glyph.advance > available && i > start {
+            // The glyph would overflow; break at the last opportunity,
+            // or mid-word if this word alone exceeds the line.
+            let split = last_break.filter(|&b| b > start).unwrap_or(i);
+            lines.push((start, split, line_width(&glyphs[start..split])));
+            start = split;
+            width = glyphs[start..=i].iter().map(|g| g.advance).sum();
+            last_break = None;
+        } else {
+            width += glyph.advance;
+        }
+        i += 1;
+    }
+    if start < glyphs.len() {
+        lines.push((start, glyphs.len(), line_width(&glyphs[start..])));
+    }
+    lines
+}
+
+/// Width of a line, excluding any trailing collapsed whitespace.
+fn line_width(glyphs: &[Measure]) -> f32 {
+    let mut end = glyphs.len();
+    while end > 0 && glyphs[end - 1].whitespace {
+        end -= 1;
+    }
+    glyphs[..end].iter().map(|g| g.advance).sum()
+}
+
+/// A text node whose shaping is deferred until an available width is
+/// known, like a constraint-based layout leaf. Shaping is the most
+/// expensive step of layout, so the last result is cached and only
+/// recomputed when the width constraint actually changes.
+pub struct MeasuredText {
+    measure: Box<dyn Fn(f32) -> EqualRc<LayoutTreeNode>>,
+    last: RefCell<Option<(f32, EqualRc<LayoutTreeNode>)>>,
+}
+
+impl MeasuredText {
+    /// Build a measured text node from its inputs. The returned closure
+    /// captures everything that affects shaping — the string contents,
+    /// the computed font/size/alignment, the resolved collection, and
+    /// the parent node it renders under — so a cached result is valid
+    /// for any width that matches.
+    pub fn new(
+        text: String,
+        values: ComputedValues,
+        collection: EqualRc<FontCollection>,
+        parent: AnyNode,
+    ) -> MeasuredText {
+        let measure = Box::new(move |width: f32| {
+            let (size, layout) = layout_text(
+                &text,
+                &values,
+                &collection,
+                LogicalSize::new(width, std::f32::INFINITY),
+            );
+            EqualRc::new(LayoutTreeNode {
+                size,
+                margin: LogicalSideOffsets::new_all_same(0.0),
+                render: RenderData::Text {
+                    text: layout,
+                    parent,
+                },
+                children: Vec::new(),
+            })
+        });
+        MeasuredText {
+            measure,
+            last: RefCell::new(None),
+        }
+    }
+
+    /// Shape against `available_width`, returning a cached result when
+    /// the width is unchanged from the previous call.
+    pub fn measure(&self, available_width: f32) -> EqualRc<LayoutTreeNode> {
+        if let Some((width, result)) = self.last.borrow().as_ref() {
+            if *width == available_width {
+                return result.clone();
+            }
+        }
+        let result = (self.measure)(available_width);
+        *self.last.borrow_mut() = Some((available_width, result.clone()));
+        result
+    }
+}
+
+/// Lay out a text child under `parent`, inheriting `values`. The
+/// measured node is memoized on the text contents and computed values,
+/// so only a change to those rebuilds it; within a rebuild the measured
+/// node re-shapes only when the available width changes.
+#[illicit::from_env(collection: &EqualRc<FontCollection>)]
+pub(super) fn layout_text_node(
+    parent: AnyNode,
+    values: &ComputedValues,
+    contents: &str,
+    available: LogicalSize,
+) -> EqualRc<LayoutTreeNode> {
+    let measured = memo!((contents.to_owned(), values.clone(), parent), |_| {
+        Rc::new(MeasuredText::new(
+            contents.to_owned(),
+            values.clone(),
+            collection.clone(),
+            parent,
+        ))
+    });
+    measured.measure(available.width)
+}
+
+/// Shape and break `text` to the given available width, producing one
+/// `LayoutLine` per visual line aligned per `values.text_align`. Returns
+/// the occupied size alongside the laid-out text.
+pub fn layout_text(
+    text: &str,
+    values: &ComputedValues,
+    collection: &EqualRc<FontCollection>,
+    available: LogicalSize,
+) -> (LogicalSize, LayoutText) {
+    let size = values.text_size.get();
+    let glyphs = shape(text, size, collection);
+    let line_ranges = break_lines(&glyphs, available.width);
+
+    // Derive vertical metrics from the shaped face's own ascent,
+    // descent and line-gap, scaled from font units into pixels. Fall
+    // back to a nominal 1.2 line height only when nothing was shaped.
+    let (line_height, ascent) = glyphs
+        .first()
+        .map(|glyph| {
+            let metrics = glyph.font.font.metrics();
+            let scale = size / metrics.units_per_em as f32;
+            let line_height =
+                (metrics.ascent - metrics.descent + metrics.line_gap) * scale;
+            (line_height, metrics.ascent * scale)
+        })
+        .unwrap_or((size * 1.2, size));
+
+    let mut lines = Vec::with_capacity(line_ranges.len());
+    let mut max_width = 0.0f32;
+    for (line_index, &(start, end, width)) in line_ranges.iter().enumerate() {
+        let is_last = line_index + 1 == line_ranges.len();
+        max_width = max_width.max(width);
+
+        // Base shift applied to every glyph on the line for alignment.
+        let slack = available.width - width;
+        let base_shift = match values.text_align {
+            TextAlign::Start | TextAlign::Justified => 0.0,
+            TextAlign::Center => slack / 2.0,
+            TextAlign::End => slack,
+        };
+
+        // Justification spreads slack across the inter-word gaps of all
+        // but the last line. The first glyph of a wrapped line may carry
+        // `break_before` (it is the first word after the break), but it
+        // opens no inter-word gap, so it is excluded from both the count
+        // and the shift below.
+        let gaps = glyphs[start..end]
+            .iter()
+            .enumerate()
+            .filter(|(i, g)| *i != 0 && g.break_before)
+            .count();
+        let justify = if values.text_align == TextAlign::Justified && !is_last && gaps > 0 {
+            slack / gaps as f32
+        } else {
+            0.0
+        };
+
+        let baseline = line_index as f32 * line_height + ascent;
+        let mut x = base_shift;
+        let mut fragments: Vec<TextFragment> = Vec::new();
+        for (i, glyph) in glyphs[start..end].iter().enumerate() {
+            if i != 0 && glyph.break_before {
+                x += justify;
+            }
+            let offset = LogicalPoint::new(x, 0.0);
+            match fragments.last_mut() {
+                Some(fragment) if std::sync::Arc::ptr_eq(&fragment.font.font, &glyph.font.font) => {
+                    fragment.glyphs.push(Glyph {
+                        index: glyph.index,
+                        offset,
+                    })
+                }
+                _ => fragments.push(TextFragment {
+                    font: glyph.font.clone(),
+                    glyphs: vec![Glyph {
+                        index: glyph.index,
+                        offset,
+                    }],
+                }),
+            }
+            x += glyph.advance;
+        }
+
+        lines.push(LayoutLine {
+            fragments,
+            baseline,
+            width,
+        });
+    }
+
+    let height = line_ranges.len() as f32 * line_height;
+    let used = LogicalSize::new(max_width.min(available.width), height);
+    (used, LayoutText { lines, size })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{break_measures, Measure};
+
+    /// Build a run of measures from `(advance, break_before, whitespace)`
+    /// triples.
+    fn run(glyphs: &[(f32, bool, bool)]) -> Vec<Measure> {
+        glyphs
+            .iter()
+            .map(|&(advance, break_before, whitespace)| Measure {
+                advance,
+                break_before,
+                whitespace,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn single_word_wider_than_line_breaks_mid_word() {
+        // No break opportunity inside the word, so it is split between
+        // glyphs wherever the next glyph would overflow.
+        let glyphs = run(&[
+            (10.0, false, false),
+            (10.0, false, false),
+            (10.0, false, false),
+        ]);
+        let lines = break_measures(&glyphs, 15.0);
+        assert_eq!(lines, vec![(0, 1, 10.0), (1, 2, 10.0), (2, 3, 10.0)]);
+    }
+
+    #[test]
+    fn trailing_whitespace_is_excluded_from_width() {
+        // "ab " — the trailing space collapses and does not count toward
+        // the reported line width.
+        let glyphs = run(&[
+            (10.0, false, false),
+            (10.0, false, false),
+            (5.0, false, true),
+        ]);
+        let lines = break_measures(&glyphs, 100.0);
+        assert_eq!(lines, vec![(0, 3, 20.0)]);
+    }
+
+    #[test]
+    fn breaks_at_last_opportunity_before_overflow() {
+        // Two words of two glyphs each with a space between; a width of
+        // 25 fits the first word and space but not the second word.
+        let glyphs = run(&[
+            (10.0, false, false),
+            (10.0, false, false),
+            (5.0, false, true),
+            (10.0, true, false),
+            (10.0, false, false),
+        ]);
+        let lines = break_measures(&glyphs, 25.0);
+        assert_eq!(lines, vec![(0, 3, 20.0), (3, 5, 20.0)]);
+    }
+
+    #[test]
+    fn exact_fit_does_not_wrap() {
+        let glyphs = run(&[(10.0, false, false), (10.0, false, false)]);
+        let lines = break_measures(&glyphs, 20.0);
+        assert_eq!(lines, vec![(0, 2, 20.0)]);
+    }
+}
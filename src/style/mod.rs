@@ -1,12 +1,57 @@
 use crate::dom::{element::DynamicNode, node::NodeRef, Node, Window};
 use crate::layout::{LogicalLength, LogicalSideOffsets, LogicalSize};
 use crate::Color;
+use font_kit::family_name::FamilyName;
+use font_kit::properties::Properties;
 use moxie::embed::Runtime;
 
 mod attributes;
 
 pub use attributes::*;
 
+/// A length that may be expressed relative to the parent content box or
+/// the element's font size. Stored unresolved in `ComputedValues` and
+/// turned into a concrete `LogicalLength` during layout, where the
+/// parent size is known.
+#[derive(Clone, PartialEq, Copy, Debug)]
+pub enum Dimension {
+    Px(f32),
+    Percent(f32),
+    Em(f32),
+    Auto,
+}
+
+impl Default for Dimension {
+    fn default() -> Self {
+        Dimension::Auto
+    }
+}
+
+impl Dimension {
+    /// Resolve to a concrete length. `parent` is the corresponding
+    /// parent content dimension (used by `Percent`) and `em` is the
+    /// element's resolved text size (used by `Em`). `Auto` resolves to
+    /// `None`, deferring to the layout algorithm's intrinsic sizing.
+    pub fn resolve(self, parent: f32, em: f32) -> Option<LogicalLength> {
+        match self {
+            Dimension::Px(px) => Some(LogicalLength::new(px)),
+            Dimension::Percent(pct) => Some(LogicalLength::new(parent * pct / 100.0)),
+            Dimension::Em(ems) => Some(LogicalLength::new(ems * em)),
+            Dimension::Auto => None,
+        }
+    }
+}
+
+/// Specifies how the lines of a text node are aligned within the
+/// available width.
+#[derive(Clone, PartialEq, Copy, Debug)]
+pub enum TextAlign {
+    Start,
+    Center,
+    End,
+    Justified,
+}
+
 /// Specifies which direction layout should be performed in.
 #[derive(Clone, PartialEq, Copy, Debug)]
 pub enum Direction {
@@ -17,17 +62,72 @@ pub enum Direction {
 #[derive(Default, PartialEq, Clone, Copy, Debug)]
 pub struct InlineValues {}
 
+/// Controls how free space is distributed between flex items along the
+/// main axis.
+#[derive(Clone, PartialEq, Copy, Debug)]
+pub enum JustifyContent {
+    FlexStart,
+    Center,
+    FlexEnd,
+    SpaceBetween,
+    SpaceAround,
+}
+
+/// Controls how flex items are aligned along the cross axis.
+#[derive(Clone, PartialEq, Copy, Debug)]
+pub enum AlignItems {
+    Stretch,
+    Start,
+    Center,
+    End,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct FlexValues {
+    pub direction: Direction,
+    pub justify_content: JustifyContent,
+    pub align_items: AlignItems,
+    pub wrap: bool,
+    pub margin: LogicalSideOffsets,
+    pub padding: LogicalSideOffsets,
+    pub width: Dimension,
+    pub height: Dimension,
+    pub min_width: Dimension,
+    pub min_height: Dimension,
+    pub max_width: Dimension,
+    pub max_height: Dimension,
+}
+
+impl Default for FlexValues {
+    fn default() -> Self {
+        FlexValues {
+            direction: Direction::Horizontal,
+            justify_content: JustifyContent::FlexStart,
+            align_items: AlignItems::Stretch,
+            wrap: false,
+            margin: LogicalSideOffsets::new_all_same(0.0),
+            padding: LogicalSideOffsets::new_all_same(0.0),
+            width: Dimension::Auto,
+            height: Dimension::Auto,
+            min_width: Dimension::Auto,
+            min_height: Dimension::Auto,
+            max_width: Dimension::Auto,
+            max_height: Dimension::Auto,
+        }
+    }
+}
+
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub struct BlockValues {
     pub direction: Direction,
     pub margin: LogicalSideOffsets,
     pub padding: LogicalSideOffsets,
-    pub width: Option<LogicalLength>,
-    pub height: Option<LogicalLength>,
-    pub min_width: Option<LogicalLength>,
-    pub min_height: Option<LogicalLength>,
-    pub max_width: Option<LogicalLength>,
-    pub max_height: Option<LogicalLength>,
+    pub width: Dimension,
+    pub height: Dimension,
+    pub min_width: Dimension,
+    pub min_height: Dimension,
+    pub max_width: Dimension,
+    pub max_height: Dimension,
 }
 
 impl Default for BlockValues {
@@ -36,12 +136,12 @@ impl Default for BlockValues {
             direction: Direction::Vertical,
             margin: LogicalSideOffsets::new_all_same(0.0),
             padding: LogicalSideOffsets::new_all_same(0.0),
-            width: None,
-            height: None,
-            min_width: None,
-            min_height: None,
-            max_width: None,
-            max_height: None,
+            width: Dimension::Auto,
+            height: Dimension::Auto,
+            min_width: Dimension::Auto,
+            min_height: Dimension::Auto,
+            max_width: Dimension::Auto,
+            max_height: Dimension::Auto,
         }
     }
 }
@@ -50,17 +150,24 @@ impl Default for BlockValues {
 pub enum DisplayType {
     Inline(InlineValues),
     Block(BlockValues),
+    Flex(FlexValues),
 }
 
-#[derive(PartialEq, Clone, Copy, Debug)]
+#[derive(PartialEq, Clone, Debug)]
 pub struct ComputedValues {
     pub display: DisplayType,
     pub text_size: LogicalLength,
+    pub text_align: TextAlign,
+    pub font_family: FamilyName,
+    pub font_properties: Properties,
     pub text_color: Color,
     pub background_color: Color,
     pub border_radius: LogicalLength,
     pub border_thickness: LogicalSideOffsets,
     pub border_color: Color,
+    pub flex_grow: f32,
+    pub flex_shrink: f32,
+    pub flex_basis: Option<LogicalLength>,
 }
 
 impl Default for ComputedValues {
@@ -68,15 +175,46 @@ impl Default for ComputedValues {
         ComputedValues {
             display: DisplayType::Block(BlockValues::default()),
             text_size: LogicalLength::new(16.0),
+            text_align: TextAlign::Start,
+            font_family: FamilyName::SansSerif,
+            font_properties: Properties::new(),
             text_color: Color::black(),
             background_color: Color::clear(),
             border_radius: LogicalLength::new(0.0),
             border_thickness: LogicalSideOffsets::new_all_same(0.0),
             border_color: Color::clear(),
+            flex_grow: 0.0,
+            flex_shrink: 1.0,
+            flex_basis: None,
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::Dimension;
+
+    #[test]
+    fn px_passes_through_ignoring_context() {
+        assert_eq!(Dimension::Px(12.0).resolve(500.0, 16.0).map(|v| v.get()), Some(12.0));
+    }
+
+    #[test]
+    fn percent_resolves_against_parent() {
+        assert_eq!(Dimension::Percent(50.0).resolve(200.0, 16.0).map(|v| v.get()), Some(100.0));
+    }
+
+    #[test]
+    fn em_multiplies_by_text_size() {
+        assert_eq!(Dimension::Em(1.5).resolve(200.0, 16.0).map(|v| v.get()), Some(24.0));
+    }
+
+    #[test]
+    fn auto_defers_to_intrinsic_sizing() {
+        assert_eq!(Dimension::Auto.resolve(200.0, 16.0), None);
+    }
+}
+
 pub struct SubStyle {
     pub selector: fn(NodeRef) -> bool,
     pub attributes: CommonAttributes,
@@ -138,6 +276,9 @@ impl StyleEngine {
 
         if let Some(parent) = parent {
             computed.text_size = parent.text_size;
+            computed.text_align = parent.text_align;
+            computed.font_family = parent.font_family.clone();
+            computed.font_properties = parent.font_properties;
             computed.text_color = parent.text_color;
         }
 
@@ -151,7 +292,7 @@ impl StyleEngine {
             }
         }
 
-        node.computed_values().set(Some(computed));
+        node.computed_values().set(Some(computed.clone()));
 
         for child in node.children() {
             if let DynamicNode::Node(node) = child {